@@ -1,10 +1,73 @@
+use std::collections::BTreeMap;
+
+use sha2::Digest;
+
 pub type Data = Vec<u8>;
 pub type Hash = Vec<u8>;
 
-pub struct MerkleTree {
-    nodes: Vec<Hash>,
+/// Domain tag prepended to leaf data before hashing, so a leaf hash can never
+/// be replayed as an internal node hash (and vice versa). See
+/// [`NODE_HASH_PREFIX`].
+pub const LEAF_HASH_PREFIX: u8 = 0x00;
+
+/// Domain tag prepended to a concatenated `left || right` pair before
+/// hashing. Distinct from [`LEAF_HASH_PREFIX`] so `verify_proof` cannot be
+/// fooled by an attacker presenting an internal node's pair as a leaf value.
+pub const NODE_HASH_PREFIX: u8 = 0x01;
+
+/// A pluggable hash function backend for [`MerkleTree`].
+///
+/// This lets the same tree logic serve protocols that disagree on hash
+/// function (SHA-256, SHA-512, Keccak, ...) without copy-pasting the tree.
+/// Implementations should domain-separate leaf hashes from node hashes (see
+/// [`Sha256Hasher`] for the reference scheme) so a leaf value can never be
+/// mistaken for an internal node's concatenated hash.
+pub trait Hasher {
+    /// Fixed-size digest produced by this hash function.
+    type Hash: Clone + PartialEq + std::fmt::Debug;
+
+    /// Hashes a single leaf's raw data.
+    fn hash_leaf(data: &[u8]) -> Self::Hash;
+
+    /// Hashes a pair of child hashes into their parent.
+    fn hash_nodes(left: &Self::Hash, right: &Self::Hash) -> Self::Hash;
+}
+
+/// Default [`Hasher`] backend: SHA-256 with [`LEAF_HASH_PREFIX`] /
+/// [`NODE_HASH_PREFIX`] domain separation.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    type Hash = Hash;
+
+    fn hash_leaf(data: &[u8]) -> Self::Hash {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update([LEAF_HASH_PREFIX]);
+        hasher.update(data);
+        hasher.finalize().to_vec()
+    }
+
+    fn hash_nodes(left: &Self::Hash, right: &Self::Hash) -> Self::Hash {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update([NODE_HASH_PREFIX]);
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().to_vec()
+    }
+}
+
+/// A Merkle tree stored level by level rather than as one flat array.
+///
+/// `levels[0]` holds the leaf hashes in insertion order and `levels.last()` always holds
+/// exactly one hash, the root. Storing each level separately (rather than packing them into a
+/// single flat array) is what lets [`Self::push_leaf`]/[`Self::update_leaf`] recompute only the
+/// handful of hashes on a leaf's path to the root instead of rebuilding the whole tree.
+pub struct MerkleTree<H: Hasher = Sha256Hasher> {
+    levels: Vec<Vec<H::Hash>>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HashDirection {
     Left,
@@ -12,25 +75,74 @@ pub enum HashDirection {
 }
 
 #[derive(Debug, Default)]
-pub struct Proof<'a> {
-    hashes: Vec<(HashDirection, &'a Hash)>,
+pub struct Proof<'a, H: Hasher = Sha256Hasher> {
+    leaf_index: usize,
+    hashes: Vec<(HashDirection, &'a H::Hash)>,
 }
 
-impl MerkleTree {
+impl<'a, H: Hasher> Proof<'a, H> {
+    /// Clones this proof's borrowed sibling hashes into an [`OwnedProof`] that can outlive the
+    /// tree it was produced from, e.g. to hand to a remote verifier.
+    pub fn to_owned(&self) -> OwnedProof<H> {
+        OwnedProof {
+            leaf_index: self.leaf_index,
+            hashes: self
+                .hashes
+                .iter()
+                .map(|(direction, hash)| (*direction, (*hash).clone()))
+                .collect(),
+        }
+    }
+}
+
+/// An owned, [`Proof`] with no borrow on the tree that produced it, so it can be serialized and
+/// sent to a verifier that never built the tree itself.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "H::Hash: serde::Serialize",
+        deserialize = "H::Hash: serde::Deserialize<'de>"
+    ))
+)]
+#[derive(Debug, Default, Clone)]
+pub struct OwnedProof<H: Hasher = Sha256Hasher> {
+    leaf_index: usize,
+    hashes: Vec<(HashDirection, H::Hash)>,
+}
+
+/// A compact proof that several leaves, identified by index, are all included in a tree.
+///
+/// Unlike a set of independent [`Proof`]s, a `MultiProof` holds only the sibling hashes that
+/// aren't themselves derivable from another requested leaf's own path, so overlapping proofs
+/// don't repeat shared hashes.
+#[derive(Debug, Default, Clone)]
+pub struct MultiProof<H: Hasher = Sha256Hasher> {
+    // Total leaf count of the tree this proof was built against. The level sizes above the
+    // leaves are a deterministic function of this count, so the verifier can retrace the same
+    // level-by-level climb the prover did without needing to know the tree's shape up front.
+    leaf_count: usize,
+    // Sibling hashes needed to recompute the root, keyed by `(level, index within level)`.
+    siblings: Vec<((usize, usize), H::Hash)>,
+}
+
+impl<H: Hasher> MerkleTree<H> {
     /// Gets root hash for this tree
-    pub fn root(&self) -> Hash {
-        self.nodes[0].clone()
+    pub fn root(&self) -> H::Hash {
+        self.levels.last().unwrap()[0].clone()
     }
 
     /// Constructs a Merkle tree from given input data
-    pub fn construct(input: &[Data]) -> MerkleTree {
-        let leaves: Vec<Hash> = input.iter().map(|v| utils::hash_data(v)).collect();
+    pub fn construct(input: &[Data]) -> MerkleTree<H> {
+        let leaves: Vec<H::Hash> = input.iter().map(|v| H::hash_leaf(v)).collect();
 
-        Self::build_tree_from_leaves(leaves.as_slice())
+        MerkleTree {
+            levels: Self::build_levels_from_leaves(leaves),
+        }
     }
 
     /// Verifies that the given input data produces the given root hash
-    pub fn verify(input: &[Data], root_hash: &Hash) -> bool {
+    pub fn verify(input: &[Data], root_hash: &H::Hash) -> bool {
         let constructed_tree = Self::construct(input);
         let constructed_root_hash = constructed_tree.root();
 
@@ -38,151 +150,310 @@ impl MerkleTree {
     }
 
     /// Verifies that the given data and proof_path correctly produce the given root_hash
-    pub fn verify_proof(data: &Data, proof: &Proof, root_hash: &Hash) -> bool {
+    pub fn verify_proof(data: &Data, proof: &Proof<H>, root_hash: &H::Hash) -> bool {
         let reconstructed_hash = proof.hashes.iter().fold(
-            utils::hash_data(data),
+            H::hash_leaf(data),
             |current_hash, (direction, sibling_hash)| match direction {
-                HashDirection::Left => utils::hash_concat(&current_hash, sibling_hash),
-                HashDirection::Right => utils::hash_concat(sibling_hash, &current_hash),
+                HashDirection::Left => H::hash_nodes(&current_hash, sibling_hash),
+                HashDirection::Right => H::hash_nodes(sibling_hash, &current_hash),
             },
         );
 
         reconstructed_hash == *root_hash
     }
 
-    /// Returns a list of hashes that can be used to prove that the given data is in this tree
-    pub fn prove(&self, data: &Data) -> Option<Proof> {
-        let mut proof_hashes = Vec::new();
+    /// Verifies that the given data and owned proof correctly produce the given root hash.
+    ///
+    /// Unlike [`Self::verify_proof`], this takes an [`OwnedProof`], so it can be called by a
+    /// party that received the proof over the wire rather than one holding a live `MerkleTree`.
+    pub fn verify_owned_proof(data: &Data, proof: &OwnedProof<H>, root_hash: &H::Hash) -> bool {
+        let reconstructed_hash = proof.hashes.iter().fold(
+            H::hash_leaf(data),
+            |current_hash, (direction, sibling_hash)| match direction {
+                HashDirection::Left => H::hash_nodes(&current_hash, sibling_hash),
+                HashDirection::Right => H::hash_nodes(sibling_hash, &current_hash),
+            },
+        );
+
+        reconstructed_hash == *root_hash
+    }
 
-        let mut current_index = self
-            .nodes
-            .iter()
-            .position(|hash| *hash == utils::hash_data(data))?;
+    /// Verifies that `data` at `leaf_index` and an owned `proof` correctly produce `root_hash`.
+    ///
+    /// The [`OwnedProof`] counterpart to [`Self::verify_proof_at`]; see that method for why
+    /// binding to a position matters for duplicate leaf values.
+    pub fn verify_owned_proof_at(
+        data: &Data,
+        leaf_index: usize,
+        proof: &OwnedProof<H>,
+        root_hash: &H::Hash,
+    ) -> bool {
+        proof.leaf_index == leaf_index && Self::verify_owned_proof(data, proof, root_hash)
+    }
 
-        while current_index > 0 {
-            let (sibling_index, direction) = if current_index % 2 == 0 {
-                (current_index - 1, HashDirection::Right)
-            } else {
-                (current_index + 1, HashDirection::Left)
-            };
+    /// Verifies that `data` at `leaf_index` and `proof` correctly produce `root_hash`.
+    ///
+    /// Unlike [`Self::verify_proof`], this binds the proof to `leaf_index`: a proof produced
+    /// by [`Self::prove_at`] for a different position is rejected even if its hash chain would
+    /// otherwise reconstruct the same root (e.g. via a duplicate leaf value).
+    pub fn verify_proof_at(
+        data: &Data,
+        leaf_index: usize,
+        proof: &Proof<H>,
+        root_hash: &H::Hash,
+    ) -> bool {
+        proof.leaf_index == leaf_index && Self::verify_proof(data, proof, root_hash)
+    }
 
-            proof_hashes.push((direction, &self.nodes[sibling_index]));
+    /// Returns a list of hashes that can be used to prove that the given data is in this tree.
+    ///
+    /// Locates the leaf by value, so if `data` appears more than once this proves the first
+    /// matching occurrence. Use [`Self::prove_at`] to prove a specific, known leaf position.
+    pub fn prove(&self, data: &Data) -> Option<Proof<'_, H>> {
+        let leaf_hash = H::hash_leaf(data);
+        let leaf_index = self.levels[0].iter().position(|hash| *hash == leaf_hash)?;
 
-            if current_index == 1 {
-                break;
-            }
+        self.prove_at(leaf_index)
+    }
 
-            current_index = (current_index - 1) / 2;
+    /// Returns a proof for the leaf at `leaf_index`, regardless of its value.
+    ///
+    /// Unlike [`Self::prove`], this binds the proof to a position rather than a value, so
+    /// duplicate leaves remain independently provable. Returns `None` if `leaf_index` is out of
+    /// range.
+    pub fn prove_at(&self, leaf_index: usize) -> Option<Proof<'_, H>> {
+        if leaf_index >= self.levels[0].len() {
+            return None;
         }
 
-        if proof_hashes.is_empty() {
-            return None;
+        let mut index = leaf_index;
+        let mut proof_hashes = Vec::new();
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling_hash) = level.get(index ^ 1) {
+                let direction = if index.is_multiple_of(2) {
+                    HashDirection::Left
+                } else {
+                    HashDirection::Right
+                };
+                proof_hashes.push((direction, sibling_hash));
+            }
+            // Else: `index` is the lone trailing node at this level, carried up unchanged, so
+            // there is no sibling hash to add here.
+
+            index /= 2;
         }
 
         Some(Proof {
+            leaf_index,
             hashes: proof_hashes,
         })
     }
 
-    ///////////////////////////////
-    /// Helpers for Exercise 1a ///
-    ///////////////////////////////
-
-    // Builds the Merkle tree from leaves
-    fn build_tree_from_leaves(leaves: &[Hash]) -> Self {
-        let count_leaves = leaves.len();
-        let count_internal_nodes = utils::next_power_of_2(count_leaves) - 1;
-        let mut nodes = vec![Vec::new(); count_internal_nodes + count_leaves];
+    /// Returns the minimal set of sibling hashes needed to prove inclusion of every leaf in
+    /// `leaf_indices` at once, without repeating a hash derivable from another requested leaf's
+    /// own path. Returns `None` for an out-of-range index.
+    pub fn prove_many(&self, leaf_indices: &[usize]) -> Option<MultiProof<H>> {
+        if leaf_indices.is_empty() {
+            return None;
+        }
 
-        // Copy leaves
-        nodes[count_internal_nodes..].clone_from_slice(leaves);
+        let leaf_count = self.levels[0].len();
+        let mut frontier: BTreeMap<usize, H::Hash> = BTreeMap::new();
 
-        // Build internal nodes
-        Self::build_internal_nodes(&mut nodes, count_internal_nodes);
+        for &leaf_index in leaf_indices {
+            frontier.insert(leaf_index, self.levels[0].get(leaf_index)?.clone());
+        }
 
-        MerkleTree { nodes }
+        let level_sizes = level_sizes(leaf_count);
+        let mut siblings = Vec::new();
+        climb_levels_to_root::<H>(&level_sizes, frontier, |level, index| {
+            let hash = self.levels.get(level)?.get(index)?.clone();
+            siblings.push(((level, index), hash.clone()));
+            Some(hash)
+        })?;
+
+        Some(MultiProof {
+            leaf_count,
+            siblings,
+        })
     }
 
-    // Internal node builder helper
-    fn build_internal_nodes(nodes: &mut Vec<Hash>, count_internal_nodes: usize) {
-        // Init..
-        let mut parent_nodes = Self::construct_upper_level(&nodes[count_internal_nodes..]);
-        if count_internal_nodes < parent_nodes.len() {
-            return;
+    /// Verifies that every `(leaf_index, data)` pair in `leaves` is included in the tree that
+    /// produced `root_hash`, using the sibling hashes carried by `proof`.
+    pub fn verify_multi_proof(
+        leaves: &[(usize, Data)],
+        proof: &MultiProof<H>,
+        root_hash: &H::Hash,
+    ) -> bool {
+        if leaves.is_empty() {
+            return false;
         }
-        let mut upper_level_start = count_internal_nodes - parent_nodes.len();
-        let mut upper_level_end = upper_level_start + parent_nodes.len();
-        nodes[upper_level_start..upper_level_end].clone_from_slice(&parent_nodes);
-
-        while parent_nodes.len() > 1 {
-            parent_nodes = Self::construct_upper_level(parent_nodes.as_slice());
-            upper_level_start -= parent_nodes.len();
-            upper_level_end = upper_level_start + parent_nodes.len();
-            nodes[upper_level_start..upper_level_end].clone_from_slice(&parent_nodes);
+
+        let mut frontier: BTreeMap<usize, H::Hash> = BTreeMap::new();
+        for (leaf_index, data) in leaves {
+            if *leaf_index >= proof.leaf_count {
+                return false;
+            }
+            frontier.insert(*leaf_index, H::hash_leaf(data));
         }
 
-        nodes[0] = parent_nodes.remove(0);
+        let level_sizes = level_sizes(proof.leaf_count);
+        let reconstructed_root =
+            climb_levels_to_root::<H>(&level_sizes, frontier, |level, index| {
+                proof
+                    .siblings
+                    .iter()
+                    .find(|((l, i), _)| *l == level && *i == index)
+                    .map(|(_, hash)| hash.clone())
+            });
+
+        reconstructed_root.as_ref() == Some(root_hash)
     }
 
-    // Constructs nodes at a certain level
-    fn construct_upper_level(nodes: &[Hash]) -> Vec<Hash> {
-        let mut count = 0_usize;
-        let mut level = Vec::with_capacity((nodes.len() + 1) / 2);
+    /// Appends a new leaf, recomputing only the hashes on its path to the root (reusing every
+    /// untouched sibling) instead of rebuilding the tree from scratch.
+    pub fn push_leaf(&mut self, data: &Data) {
+        let mut hash = H::hash_leaf(data);
+        self.levels[0].push(hash.clone());
+
+        let mut index = self.levels[0].len() - 1;
+        let mut level = 0;
+
+        // Climbs from the new leaf all the way to the root: each step either combines with an
+        // already-existing sibling or, when there is none, carries the hash up unchanged,
+        // overwriting the entry at the parent position or appending it as a new entry (and
+        // adding a new top level when the old root gains a sibling). Stops at the level that
+        // holds a single hash, the root.
+        loop {
+            if self.levels[level].len() == 1 {
+                break;
+            }
 
-        while count + 1 < nodes.len() {
-            level.push(Self::hash_internal_node(
-                &nodes[count],
-                Some(&nodes[count + 1]),
-            ));
-            count += 2;
+            hash = match self.levels[level].get(index ^ 1) {
+                Some(sibling) if index.is_multiple_of(2) => H::hash_nodes(&hash, sibling),
+                Some(sibling) => H::hash_nodes(sibling, &hash),
+                None => hash,
+            };
+
+            index /= 2;
+            level += 1;
+            if level == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            if index < self.levels[level].len() {
+                self.levels[level][index] = hash.clone();
+            } else {
+                self.levels[level].push(hash.clone());
+            }
         }
+    }
 
-        if count < nodes.len() {
-            level.push(nodes[count].clone());
+    /// Replaces the leaf at `leaf_index`, recomputing only the hashes on its path to the root
+    /// (reusing every untouched sibling) instead of rebuilding the tree from scratch. Returns
+    /// `false` if `leaf_index` is out of range.
+    pub fn update_leaf(&mut self, leaf_index: usize, data: &Data) -> bool {
+        if leaf_index >= self.levels[0].len() {
+            return false;
         }
 
-        level
-    }
+        let mut hash = H::hash_leaf(data);
+        self.levels[0][leaf_index] = hash.clone();
+        let mut index = leaf_index;
 
-    fn hash_internal_node(left: &Hash, right: Option<&Hash>) -> Hash {
-        if let Some(right) = right {
-            utils::hash_concat(left, right)
-        } else {
-            utils::hash_data(left)
+        for level in 0..self.levels.len() - 1 {
+            hash = match self.levels[level].get(index ^ 1) {
+                Some(sibling) if index.is_multiple_of(2) => H::hash_nodes(&hash, sibling),
+                Some(sibling) => H::hash_nodes(sibling, &hash),
+                None => hash,
+            };
+
+            index /= 2;
+            self.levels[level + 1][index] = hash.clone();
         }
+
+        true
     }
-}
 
-mod utils {
-    use crate::{Data, Hash};
-    use sha2::Digest;
+    // Builds each level bottom-up from the leaf hashes, combining adjacent pairs and carrying
+    // an odd trailing node up unchanged, until a single root hash remains.
+    fn build_levels_from_leaves(leaves: Vec<H::Hash>) -> Vec<Vec<H::Hash>> {
+        let mut levels = vec![leaves];
+
+        while levels.last().unwrap().len() > 1 {
+            let current = levels.last().unwrap();
+            let mut next = Vec::with_capacity(current.len().div_ceil(2));
+
+            let mut count = 0;
+            while count + 1 < current.len() {
+                next.push(H::hash_nodes(&current[count], &current[count + 1]));
+                count += 2;
+            }
+            if count < current.len() {
+                next.push(current[count].clone());
+            }
+
+            levels.push(next);
+        }
 
-    pub(crate) fn hash_data(data: &Data) -> Hash {
-        sha2::Sha256::digest(data).to_vec()
+        levels
     }
+}
 
-    pub(crate) fn hash_concat(h1: &Hash, h2: &Hash) -> Hash {
-        let h3 = h1.iter().chain(h2).copied().collect();
-        hash_data(&h3)
+// The size of each level above a tree's leaves is a deterministic function of the leaf count
+// (halving, rounding up, at each step), so both the prover and the verifier can derive it
+// without needing to exchange the tree's shape.
+fn level_sizes(leaf_count: usize) -> Vec<usize> {
+    let mut sizes = vec![leaf_count];
+    while *sizes.last().unwrap() > 1 {
+        sizes.push(sizes.last().unwrap().div_ceil(2));
     }
+    sizes
+}
+
+// Shared by `prove_many`/`verify_multi_proof`: repeatedly combines a frontier of known
+// `(index within level -> hash)` pairs with their siblings until a single root hash remains,
+// fetching any sibling the frontier doesn't already carry via `sibling_lookup`. Returns `None`
+// if a needed sibling is unavailable (out of range or missing from the proof).
+fn climb_levels_to_root<H: Hasher>(
+    level_sizes: &[usize],
+    mut frontier: BTreeMap<usize, H::Hash>,
+    mut sibling_lookup: impl FnMut(usize, usize) -> Option<H::Hash>,
+) -> Option<H::Hash> {
+    for (level, &size) in level_sizes.iter().enumerate().take(level_sizes.len() - 1) {
+        let mut next_frontier: BTreeMap<usize, H::Hash> = BTreeMap::new();
+
+        for (&index, hash) in frontier.iter() {
+            let parent_index = index / 2;
+            if next_frontier.contains_key(&parent_index) {
+                continue;
+            }
 
-    ///////////////
-    /// Helpers ///
-    ///////////////
+            if index == size - 1 && size % 2 == 1 {
+                // Lone trailing node at this level: carried up unchanged, no sibling needed.
+                next_frontier.insert(parent_index, hash.clone());
+                continue;
+            }
 
-    pub(crate) fn next_power_of_2(input: usize) -> usize {
-        let mut val = input;
+            let sibling_index = index ^ 1;
+            let sibling_hash = match frontier.get(&sibling_index) {
+                Some(hash) => hash.clone(),
+                None => sibling_lookup(level, sibling_index)?,
+            };
+
+            let parent_hash = if index % 2 == 0 {
+                H::hash_nodes(hash, &sibling_hash)
+            } else {
+                H::hash_nodes(&sibling_hash, hash)
+            };
 
-        val -= 1;
-        val |= val >> 1;
-        val |= val >> 2;
-        val |= val >> 4;
-        val |= val >> 8;
-        val |= val >> 16;
-        val += 1;
+            next_frontier.insert(parent_index, parent_hash);
+        }
 
-        val
+        frontier = next_frontier;
     }
+
+    frontier.remove(&0)
 }
 
 mod tests {
@@ -197,6 +468,48 @@ mod tests {
         data
     }
 
+    // A minimal second `Hasher` backend, used only to confirm `MerkleTree`/`Proof` genuinely
+    // work when `H` isn't `Sha256Hasher`, rather than assuming genericity from the types
+    // compiling.
+    #[allow(dead_code)]
+    #[derive(Debug, Default, Clone, Copy)]
+    struct FnvHasher;
+
+    impl Hasher for FnvHasher {
+        type Hash = u64;
+
+        fn hash_leaf(data: &[u8]) -> Self::Hash {
+            let mut hash = u64::from(LEAF_HASH_PREFIX);
+            for &byte in data {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+            }
+            hash
+        }
+
+        fn hash_nodes(left: &Self::Hash, right: &Self::Hash) -> Self::Hash {
+            let mut hash = u64::from(NODE_HASH_PREFIX);
+            hash = (hash ^ left).wrapping_mul(0x100000001b3);
+            hash = (hash ^ right).wrapping_mul(0x100000001b3);
+            hash
+        }
+    }
+
+    #[test]
+    fn test_generic_over_custom_hasher() {
+        let data = example_data(4);
+        let tree: MerkleTree<FnvHasher> = MerkleTree::construct(&data);
+
+        assert!(MerkleTree::<FnvHasher>::verify(&data, &tree.root()));
+
+        let proof = tree.prove_at(2).unwrap();
+        assert!(MerkleTree::<FnvHasher>::verify_proof_at(
+            &data[2],
+            2,
+            &proof,
+            &tree.root()
+        ));
+    }
+
     #[test]
     fn test_constructions() {
         //////////////////////////////
@@ -204,8 +517,8 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(4);
-        let tree = MerkleTree::construct(&data);
-        let expected_root = "9675e04b4ba9dc81b06e81731e2d21caa2c95557a85dcfa3fff70c9ff0f30b2e";
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        let expected_root = "9bcd51240af4005168f033121ba85be5a6ed4f0e6a5fac262066729b8fbfdecb";
         assert_eq!(hex::encode(tree.root()), expected_root);
 
         ///////////////////////////////
@@ -213,8 +526,8 @@ mod tests {
         ///////////////////////////////
 
         let data = example_data(3);
-        let tree = MerkleTree::construct(&data);
-        let expected_root = "773a93ac37ea78b3f14ac31872c83886b0a0f1fec562c4e848e023c889c2ce9f";
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        let expected_root = "3b6cccd7e3e023ff393006f030315ee7ad9eb111b022b41fba7e5b7a3973f688";
         assert_eq!(hex::encode(tree.root()), expected_root);
 
         //////////////////////////////
@@ -222,8 +535,8 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(8);
-        let tree = MerkleTree::construct(&data);
-        let expected_root = "0727b310f87099c1ba2ec0ba408def82c308237c8577f0bdfd2643e9cc6b7578";
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        let expected_root = "ef7f49b620f6c7ea9b963a214da34b5021c6ded8ed57734380a311ab726aa907";
         assert_eq!(hex::encode(tree.root()), expected_root);
     }
 
@@ -234,32 +547,32 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(4);
-        let tree = MerkleTree::construct(&data);
-        assert!(MerkleTree::verify(&data, &tree.root()));
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        assert!(MerkleTree::<Sha256Hasher>::verify(&data, &tree.root()));
 
         ///////////////////////////////
         // Data set 2 (!Power of 2) ///
         ///////////////////////////////
 
         let data = example_data(3);
-        let tree = MerkleTree::construct(&data);
-        assert!(MerkleTree::verify(&data, &tree.root()));
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        assert!(MerkleTree::<Sha256Hasher>::verify(&data, &tree.root()));
 
         //////////////////////////////
         // Data set 3 (Power of 2) ///
         //////////////////////////////
 
         let data = example_data(8);
-        let tree = MerkleTree::construct(&data);
-        assert!(MerkleTree::verify(&data, &tree.root()));
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        assert!(MerkleTree::<Sha256Hasher>::verify(&data, &tree.root()));
 
         ///////////////////////////////
         // Data set 4 (!Power of 2) ///
         ///////////////////////////////
 
         let data = example_data(1);
-        let tree = MerkleTree::construct(&data);
-        assert!(MerkleTree::verify(&data, &tree.root()));
+        let tree: MerkleTree = MerkleTree::construct(&data);
+        assert!(MerkleTree::<Sha256Hasher>::verify(&data, &tree.root()));
     }
 
     #[test]
@@ -269,7 +582,7 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(4);
-        let tree = MerkleTree::construct(&data);
+        let tree: MerkleTree = MerkleTree::construct(&data);
 
         // Test proof generation and verification, index 2
         let proof = tree.prove(&data[2]).unwrap();
@@ -284,7 +597,7 @@ mod tests {
         ///////////////////////////////
 
         let data = example_data(3);
-        let tree = MerkleTree::construct(&data);
+        let tree: MerkleTree = MerkleTree::construct(&data);
 
         // Test proof generation and verification, index 1
         let proof = tree.prove(&data[1]).unwrap();
@@ -299,7 +612,7 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(8);
-        let tree = MerkleTree::construct(&data);
+        let tree: MerkleTree = MerkleTree::construct(&data);
 
         // Test proof generation and verification, index 4
         let proof = tree.prove(&data[4]).unwrap();
@@ -317,7 +630,7 @@ mod tests {
         ///////////////////////////////
 
         let data = example_data(3);
-        let tree = MerkleTree::construct(&data);
+        let tree: MerkleTree = MerkleTree::construct(&data);
 
         // Test proof generation index 1
         let proof = tree.prove(&data[1]).unwrap();
@@ -332,7 +645,7 @@ mod tests {
         //////////////////////////////
 
         let data = example_data(4);
-        let tree = MerkleTree::construct(&data);
+        let tree: MerkleTree = MerkleTree::construct(&data);
 
         // Test proof generation index 2
         let proof = tree.prove(&data[2]).unwrap();
@@ -342,4 +655,226 @@ mod tests {
             false
         );
     }
+
+    #[test]
+    fn test_prove_at_duplicate_leaves() {
+        // Two leaves share the same value, so value-based `prove` can only ever reach index 1.
+        let data = vec![vec![0u8], vec![7u8], vec![7u8], vec![3u8]];
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let proof_at_1 = tree.prove_at(1).unwrap();
+        let proof_at_2 = tree.prove_at(2).unwrap();
+
+        assert!(MerkleTree::verify_proof_at(
+            &data[1],
+            1,
+            &proof_at_1,
+            &tree.root()
+        ));
+        assert!(MerkleTree::verify_proof_at(
+            &data[2],
+            2,
+            &proof_at_2,
+            &tree.root()
+        ));
+
+        // A proof generated for index 2 does not bind to index 1, even though the leaf value
+        // and hash chain are identical.
+        assert!(!MerkleTree::verify_proof_at(
+            &data[1],
+            1,
+            &proof_at_2,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_prove_at_out_of_range() {
+        let data = example_data(4);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        assert!(tree.prove_at(4).is_none());
+    }
+
+    #[test]
+    fn test_prove_at_non_power_of_two_leaf_count() {
+        // The trailing leaf of a non-power-of-2 tree is the one `build_levels_from_leaves`
+        // carries up unchanged, so it exercises a different code path than the other leaves.
+        let data = example_data(3);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        for (leaf_index, leaf_data) in data.iter().enumerate() {
+            let proof = tree.prove_at(leaf_index).unwrap();
+            assert!(MerkleTree::verify_proof_at(
+                leaf_data,
+                leaf_index,
+                &proof,
+                &tree.root()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_prove_many_success() {
+        let data = example_data(8);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let proof = tree.prove_many(&[1, 4, 4, 6]).unwrap();
+        let leaves: Vec<(usize, Data)> = vec![
+            (4, data[4].clone()),
+            (1, data[1].clone()),
+            (6, data[6].clone()),
+        ];
+        assert!(MerkleTree::verify_multi_proof(
+            &leaves,
+            &proof,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_prove_many_rejects_wrong_leaf() {
+        let data = example_data(8);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let proof = tree.prove_many(&[1, 6]).unwrap();
+        let leaves: Vec<(usize, Data)> = vec![(1, data[1].clone()), (6, data[2].clone())];
+        assert!(!MerkleTree::verify_multi_proof(
+            &leaves,
+            &proof,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_prove_many_rejects_out_of_range() {
+        let data = example_data(4);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        assert!(tree.prove_many(&[0, 4]).is_none());
+    }
+
+    #[test]
+    fn test_prove_many_non_power_of_two_leaf_count() {
+        // Leaf 2 is the trailing node `build_levels_from_leaves` carries up unchanged, so it
+        // exercises a different code path than a pair of ordinarily-combined leaves.
+        let data = example_data(3);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let proof = tree.prove_many(&[2]).unwrap();
+        let leaves: Vec<(usize, Data)> = vec![(2, data[2].clone())];
+        assert!(MerkleTree::verify_multi_proof(
+            &leaves,
+            &proof,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_push_leaf_matches_full_rebuild() {
+        let mut data = example_data(3);
+        let mut tree: MerkleTree = MerkleTree::construct(&data);
+
+        // Push leaves one at a time across several power-of-2 boundaries and check that the
+        // incrementally-updated tree always matches a from-scratch rebuild.
+        for i in 3..10 {
+            let leaf = vec![i as u8];
+            tree.push_leaf(&leaf);
+            data.push(leaf);
+
+            let rebuilt: MerkleTree = MerkleTree::construct(&data);
+            assert_eq!(tree.root(), rebuilt.root());
+        }
+    }
+
+    #[test]
+    fn test_push_leaf_keeps_prove_and_verify_proof_working() {
+        let data = example_data(4);
+        let mut tree: MerkleTree = MerkleTree::construct(&data);
+
+        let pushed = vec![42u8];
+        tree.push_leaf(&pushed);
+
+        let proof = tree.prove(&pushed).unwrap();
+        assert!(MerkleTree::verify_proof(&pushed, &proof, &tree.root()));
+
+        // Existing leaves remain provable too.
+        let proof = tree.prove(&data[1]).unwrap();
+        assert!(MerkleTree::verify_proof(&data[1], &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_update_leaf_matches_full_rebuild() {
+        let mut data = example_data(5);
+        let mut tree: MerkleTree = MerkleTree::construct(&data);
+
+        let new_leaf = vec![99u8];
+        assert!(tree.update_leaf(2, &new_leaf));
+        data[2] = new_leaf;
+
+        let rebuilt: MerkleTree = MerkleTree::construct(&data);
+        assert_eq!(tree.root(), rebuilt.root());
+
+        let proof = tree.prove(&data[2]).unwrap();
+        assert!(MerkleTree::verify_proof(&data[2], &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_update_leaf_out_of_range() {
+        let data = example_data(4);
+        let mut tree: MerkleTree = MerkleTree::construct(&data);
+
+        assert!(!tree.update_leaf(4, &vec![0u8]));
+    }
+
+    #[test]
+    fn test_owned_proof_round_trip() {
+        let data = example_data(4);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let proof = tree.prove_at(2).unwrap();
+        let owned = proof.to_owned();
+
+        assert!(MerkleTree::verify_owned_proof(&data[2], &owned, &tree.root()));
+        assert!(MerkleTree::verify_owned_proof_at(
+            &data[2],
+            2,
+            &owned,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_owned_proof_rejects_wrong_index() {
+        // Two leaves share the same value, so the owned proof must still bind to its position.
+        let data = vec![vec![0u8], vec![7u8], vec![7u8], vec![3u8]];
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let owned = tree.prove_at(2).unwrap().to_owned();
+
+        assert!(!MerkleTree::verify_owned_proof_at(
+            &data[1],
+            1,
+            &owned,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_owned_proof_serde_round_trip() {
+        let data = example_data(4);
+        let tree: MerkleTree = MerkleTree::construct(&data);
+
+        let owned = tree.prove_at(2).unwrap().to_owned();
+        let serialized = serde_json::to_vec(&owned).unwrap();
+        let deserialized: OwnedProof = serde_json::from_slice(&serialized).unwrap();
+
+        assert!(MerkleTree::verify_owned_proof_at(
+            &data[2],
+            2,
+            &deserialized,
+            &tree.root()
+        ));
+    }
 }